@@ -1,3 +1,219 @@
+/// A [`log::Log`] implementation that routes records from the `log` facade (`log::info!`,
+/// `log::debug!`, etc.) into nginx's error log.
+///
+/// Install it once per worker process, typically from the module's init callback:
+///
+/// ```ignore
+/// let log = unsafe { (*ngx_cycle).log };
+/// ngx_rust_static::log::init_global_logger(log).expect("logger already installed");
+/// ```
+///
+/// # Safety
+///
+/// `NgxLogger` stores a raw `*mut ngx_log_t` rather than looking one up per call, because
+/// there is no globally accessible `ngx_log_t` the `log` facade could hand back to us. That
+/// pointer is only valid for the lifetime of the worker process (and cycle) it was taken
+/// from: nginx allocates a fresh `ngx_log_t` on every configuration reload and worker
+/// respawn, so a logger installed before a reload must not keep being used afterwards.
+/// Re-run [`init_global_logger`] with the new cycle's log after each reload instead of
+/// relying on the previous instance.
+#[cfg(feature = "log")]
+pub struct NgxLogger {
+    log: *mut crate::ffi::ngx_log_t,
+}
+
+#[cfg(feature = "log")]
+impl NgxLogger {
+    /// Create a logger that writes through `log`.
+    pub fn new(log: *mut crate::ffi::ngx_log_t) -> Self {
+        NgxLogger { log }
+    }
+
+    /// The [`log::LevelFilter`] matching this log's current `log_level`, suitable for
+    /// [`log::set_max_level`] so that `log::max_level()` tracks that `log_level`. At a
+    /// `log_level` exactly equal to a level's value the filter admits that level while
+    /// [`enabled`](log::Log::enabled) does not; `log_enabled!` consults both and agrees with
+    /// what the logger emits.
+    pub fn max_level(&self) -> log::LevelFilter {
+        ngx_log_level_to_filter(unsafe { (*self.log).log_level })
+    }
+}
+
+// `ngx_log_t` is only mutated during configuration parsing, well before a logger could be
+// installed, so sharing a read-only pointer to it across threads is sound.
+#[cfg(feature = "log")]
+unsafe impl Send for NgxLogger {}
+#[cfg(feature = "log")]
+unsafe impl Sync for NgxLogger {}
+
+#[cfg(feature = "log")]
+impl log::Log for NgxLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let level = ngx_log_level(metadata.level());
+        level < unsafe { (*self.log).log_level }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = ngx_log_level(record.level());
+        let message = ::std::format!("{}", record.args());
+        let message = message.as_bytes();
+        unsafe {
+            crate::ffi::ngx_log_error_core(level, self.log, 0, c"%*s".as_ptr(), message.len(), message.as_ptr());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Maps a [`log::Level`] onto the nginx `NGX_LOG_*` severity it is emitted at. nginx has no
+/// severity more verbose than `NGX_LOG_DEBUG`, so both `Debug` and `Trace` map onto it.
+#[cfg(feature = "log")]
+fn ngx_log_level(level: log::Level) -> crate::ffi::ngx_uint_t {
+    (match level {
+        log::Level::Error => crate::ffi::NGX_LOG_ERR,
+        log::Level::Warn => crate::ffi::NGX_LOG_WARN,
+        log::Level::Info => crate::ffi::NGX_LOG_INFO,
+        log::Level::Debug => crate::ffi::NGX_LOG_DEBUG,
+        log::Level::Trace => crate::ffi::NGX_LOG_DEBUG,
+    }) as crate::ffi::ngx_uint_t
+}
+
+/// Inverse of [`ngx_log_level`]: the most verbose `log::LevelFilter` whose nginx severity
+/// is `<= log_level`. [`NgxLogger`]'s `enabled` uses the strict `level < log_level` check of
+/// [`ngx_log_error!`], so when `log_level` equals a level's value exactly, this filter
+/// admits that level but `enabled` still drops its records.
+#[cfg(feature = "log")]
+fn ngx_log_level_to_filter(log_level: crate::ffi::ngx_uint_t) -> log::LevelFilter {
+    if log_level >= crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t {
+        log::LevelFilter::Trace
+    } else if log_level >= crate::ffi::NGX_LOG_INFO as crate::ffi::ngx_uint_t {
+        log::LevelFilter::Info
+    } else if log_level >= crate::ffi::NGX_LOG_WARN as crate::ffi::ngx_uint_t {
+        log::LevelFilter::Warn
+    } else if log_level >= crate::ffi::NGX_LOG_ERR as crate::ffi::ngx_uint_t {
+        log::LevelFilter::Error
+    } else {
+        log::LevelFilter::Off
+    }
+}
+
+/// Installs an [`NgxLogger`] backed by `log` as the global logger for the `log` facade, and
+/// raises [`log::set_max_level`] to match so `log_enabled!` agrees with what the logger
+/// will actually emit.
+///
+/// Requires the `log` feature. See [`NgxLogger`] for the pointer's lifetime constraints.
+#[cfg(feature = "log")]
+pub fn init_global_logger(log: *mut crate::ffi::ngx_log_t) -> Result<(), log::SetLoggerError> {
+    let logger = NgxLogger::new(log);
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(logger))
+}
+
+/// The most verbose level [`ngx_log_error!`] and [`ngx_log_debug!`] will compile in,
+/// mirroring the `log` crate's `STATIC_MAX_LEVEL`. Calls at a less severe level than this
+/// const are guarded by `if $level <= NGX_LOG_STATIC_MAX_LEVEL` in addition to the runtime
+/// `log_level` check, so the optimizer can remove the `format!` call and FFI call entirely
+/// for levels compiled out -- this is what lets `ngx_log_debug!` disappear from release
+/// dynamic modules instead of merely going silent.
+///
+/// Controlled by cargo features, checked in order of precedence (most restrictive wins):
+/// `max_level_off`, `max_level_error`, `max_level_warn`, `max_level_info`,
+/// `max_level_debug` in debug builds (`cfg(debug_assertions)`), and the `release_max_level_*`
+/// equivalents in release builds. Defaults to [`NGX_LOG_DEBUG`](crate::ffi::NGX_LOG_DEBUG)
+/// (nothing stripped) if no feature is enabled.
+#[cfg(all(debug_assertions, feature = "max_level_off"))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = 0;
+#[cfg(all(debug_assertions, feature = "max_level_error", not(feature = "max_level_off")))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_ERR as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    debug_assertions,
+    feature = "max_level_warn",
+    not(any(feature = "max_level_off", feature = "max_level_error"))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_WARN as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    debug_assertions,
+    feature = "max_level_info",
+    not(any(feature = "max_level_off", feature = "max_level_error", feature = "max_level_warn"))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_INFO as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    debug_assertions,
+    feature = "max_level_debug",
+    not(any(
+        feature = "max_level_off",
+        feature = "max_level_error",
+        feature = "max_level_warn",
+        feature = "max_level_info"
+    ))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t;
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_off"))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = 0;
+#[cfg(all(
+    not(debug_assertions),
+    feature = "release_max_level_error",
+    not(feature = "release_max_level_off")
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_ERR as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    not(debug_assertions),
+    feature = "release_max_level_warn",
+    not(any(feature = "release_max_level_off", feature = "release_max_level_error"))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_WARN as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    not(debug_assertions),
+    feature = "release_max_level_info",
+    not(any(
+        feature = "release_max_level_off",
+        feature = "release_max_level_error",
+        feature = "release_max_level_warn"
+    ))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_INFO as crate::ffi::ngx_uint_t;
+#[cfg(all(
+    not(debug_assertions),
+    feature = "release_max_level_debug",
+    not(any(
+        feature = "release_max_level_off",
+        feature = "release_max_level_error",
+        feature = "release_max_level_warn",
+        feature = "release_max_level_info"
+    ))
+))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t;
+
+// Fallback when no `max_level_*`/`release_max_level_*` feature matched the current profile:
+// nothing is compiled out.
+#[cfg(not(any(
+    all(
+        debug_assertions,
+        any(
+            feature = "max_level_off",
+            feature = "max_level_error",
+            feature = "max_level_warn",
+            feature = "max_level_info",
+            feature = "max_level_debug"
+        )
+    ),
+    all(
+        not(debug_assertions),
+        any(
+            feature = "release_max_level_off",
+            feature = "release_max_level_error",
+            feature = "release_max_level_warn",
+            feature = "release_max_level_info",
+            feature = "release_max_level_debug"
+        )
+    )
+)))]
+pub const NGX_LOG_STATIC_MAX_LEVEL: crate::ffi::ngx_uint_t = crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t;
+
 /// Utility function to provide typed checking of the mask's field state.
 #[inline(always)]
 pub fn check_mask(mask: DebugMask, log_level: usize) -> bool {
@@ -8,16 +224,53 @@ pub fn check_mask(mask: DebugMask, log_level: usize) -> bool {
     true
 }
 
+/// Appends `key=value` to `message`, quoting the value if its rendering contains a space.
+/// Used by the structured key-value arms of [`ngx_log_error!`], [`ngx_log_debug!`], and
+/// [`ngx_log_debug_http!`] to give the `log` crate's `key = value, ...;` syntax a stable,
+/// single-line rendering inside nginx's error log.
+#[doc(hidden)]
+pub fn push_kv(message: &mut String, key: &str, value: &dyn ::std::fmt::Display) {
+    use ::std::fmt::Write;
+    let value = value.to_string();
+    if value.contains(' ') {
+        // Escape embedded backslashes and quotes so the surrounding quoting stays
+        // unambiguous for anything parsing the rendered key=value pairs back out.
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = write!(message, " {key}=\"{escaped}\"");
+    } else {
+        let _ = write!(message, " {key}={value}");
+    }
+}
+
 /// Write to logger at a specified level.
 ///
 /// See [Logging](https://nginx.org/en/docs/dev/development_guide.html#logging)
 /// for available log levels.
+///
+/// Optional structured key-value pairs may precede the format string, mirroring the `log`
+/// crate's `log!(target: ..., Level::Info, key1 = 42, key2 = true; "msg")` syntax:
+///
+/// ```ignore
+/// ngx_log_error!(NGX_LOG_ERR, log, upstream = "backend", status = 502; "bad gateway");
+/// ```
 #[macro_export]
 macro_rules! ngx_log_error {
+    ( $level:expr, $log:expr, $($key:tt = $value:expr),+ ; $($arg:tt)+ ) => {
+        let log = $log;
+        let level = $level as $crate::ffi::ngx_uint_t;
+        if level <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL && level < unsafe { (*log).log_level } {
+            let mut message = ::std::format!($($arg)+);
+            $( $crate::log::push_kv(&mut message, ::std::stringify!($key), &$value); )+
+            let message = message.as_bytes();
+            unsafe {
+                $crate::ffi::ngx_log_error_core(level, log, 0, c"%*s".as_ptr(), message.len(), message.as_ptr());
+            }
+        }
+    };
     ( $level:expr, $log:expr, $($arg:tt)+ ) => {
         let log = $log;
         let level = $level as $crate::ffi::ngx_uint_t;
-        if level < unsafe { (*log).log_level } {
+        if level <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL && level < unsafe { (*log).log_level } {
             let message = ::std::format!($($arg)+);
             let message = message.as_bytes();
             unsafe {
@@ -44,12 +297,27 @@ macro_rules! ngx_conf_log_error {
 }
 
 /// Write to logger at debug level.
+///
+/// Optional structured key-value pairs may precede the format string; see
+/// [`ngx_log_error!`] for the syntax.
 #[macro_export]
 macro_rules! ngx_log_debug {
+    ( mask: $mask:expr, $log:expr, $($key:tt = $value:expr),+ ; $($arg:tt)+ ) => {
+        let log = $log;
+        let level = $crate::ffi::NGX_LOG_DEBUG as $crate::ffi::ngx_uint_t;
+        if level <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL && $crate::log::check_mask($mask, unsafe { (*log).log_level }) {
+            let mut message = format!($($arg)+);
+            $( $crate::log::push_kv(&mut message, ::std::stringify!($key), &$value); )+
+            let message = message.as_bytes();
+            unsafe {
+                $crate::ffi::ngx_log_error_core(level, log, 0, c"%*s".as_ptr(), message.len(), message.as_ptr());
+            }
+        }
+    };
     ( mask: $mask:expr, $log:expr, $($arg:tt)+ ) => {
         let log = $log;
-        if $crate::log::check_mask($mask, unsafe { (*log).log_level }) {
-            let level = $crate::ffi::NGX_LOG_DEBUG as $crate::ffi::ngx_uint_t;
+        let level = $crate::ffi::NGX_LOG_DEBUG as $crate::ffi::ngx_uint_t;
+        if level <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL && $crate::log::check_mask($mask, unsafe { (*log).log_level }) {
             let message = format!($($arg)+);
             let message = message.as_bytes();
             unsafe {
@@ -57,6 +325,9 @@ macro_rules! ngx_log_debug {
             }
         }
     };
+    ( $log:expr, $($key:tt = $value:expr),+ ; $($arg:tt)+ ) => {
+        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::All, $log, $($key = $value),+ ; $($arg)+);
+    };
     ( $log:expr, $($arg:tt)+ ) => {
         $crate::ngx_log_debug!(mask: $crate::log::DebugMask::All, $log, $($arg)+);
     }
@@ -64,100 +335,223 @@ macro_rules! ngx_log_debug {
 
 /// Log to request connection log at level [`NGX_LOG_DEBUG_HTTP`].
 ///
+/// Optional structured key-value pairs may precede the format string; see
+/// [`ngx_log_error!`] for the syntax.
+///
 /// [`NGX_LOG_DEBUG_HTTP`]: https://nginx.org/en/docs/dev/development_guide.html#logging
 #[macro_export]
 macro_rules! ngx_log_debug_http {
+    ( $request:expr, $($key:tt = $value:expr),+ ; $($arg:tt)+ ) => {
+        let log = unsafe { (*$request.connection()).log };
+        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Http, log, $($key = $value),+ ; $($arg)+);
+    };
     ( $request:expr, $($arg:tt)+ ) => {
         let log = unsafe { (*$request.connection()).log };
         $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Http, log, $($arg)+);
     }
 }
 
+/// Cheaply check whether a log would actually emit at the given level (or debug mask)
+/// before doing work that only exists to produce log arguments, analogous to the `log`
+/// crate's `log_enabled!`:
+///
+/// ```ignore
+/// if ngx_log_enabled!(mask: DebugMask::Http, log) {
+///     let dump = expensive_to_compute();
+///     ngx_log_debug!(mask: DebugMask::Http, log, "{dump}");
+/// }
+/// ```
+#[macro_export]
+macro_rules! ngx_log_enabled {
+    ( mask: $mask:expr, $log:expr ) => {
+        ($crate::ffi::NGX_LOG_DEBUG as $crate::ffi::ngx_uint_t) <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL
+            && $crate::log::check_mask($mask, unsafe { (*$log).log_level })
+    };
+    ( $level:expr, $log:expr ) => {
+        ($level as $crate::ffi::ngx_uint_t) <= $crate::log::NGX_LOG_STATIC_MAX_LEVEL
+            && ($level as $crate::ffi::ngx_uint_t) < unsafe { (*$log).log_level }
+    };
+}
+
 /// Log with requested debug mask.
 ///
 /// **NOTE:** This macro supports [`DebugMask::Http`] (`NGX_LOG_DEBUG_HTTP`), however, if you have
 /// access to a Request object via an http handler it can be more convenient and readable to use
 /// the [`ngx_log_debug_http`] macro instead.
 ///
+/// `$mask` may be any expression producing a [`DebugMask`], including a combination such as
+/// `DebugMask::Http | DebugMask::Event`.
+///
 /// See <https://nginx.org/en/docs/dev/development_guide.html#logging> for details and available
 /// masks.
 #[macro_export]
 macro_rules! ngx_log_debug_mask {
-    ( DebugMask::Core, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Core, $log, $($arg)+);
-    };
-    ( DebugMask::Alloc, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Alloc, $log, $($arg)+);
-    };
-    ( DebugMask::Mutex, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Mutex, $log, $($arg)+);
-    };
-    ( DebugMask::Event, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Event, $log, $($arg)+);
-    };
-    ( DebugMask::Http, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Http, $log, $($arg)+);
-    };
-    ( DebugMask::Mail, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Mail, $log, $($arg)+);
-    };
-    ( DebugMask::Stream, $log:expr, $($arg:tt)+ ) => {
-        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Stream, $log, $($arg)+);
+    ( $mask:expr, $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_debug!(mask: $mask, $log, $($arg)+);
     };
 }
 
-/// Debug masks for use with [`ngx_log_debug_mask`], these represent the only accepted values for
-/// the mask.
-#[derive(Debug)]
-pub enum DebugMask {
+/// Debug masks for use with [`ngx_log_debug_mask`].
+///
+/// Backed by a bitset rather than a plain enum so masks can be combined with `|`/`&`, e.g.
+/// `DebugMask::Http | DebugMask::Event`, and so the set of masks enabled by a log's
+/// `log_level` can be reconstructed losslessly (see [`From<u32>`](#impl-From<u32>-for-DebugMask)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugMask(u32);
+
+#[allow(non_upper_case_globals)]
+impl DebugMask {
     /// Aligns to the NGX_LOG_DEBUG_CORE mask.
-    Core,
+    pub const Core: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_CORE);
     /// Aligns to the NGX_LOG_DEBUG_ALLOC mask.
-    Alloc,
+    pub const Alloc: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_ALLOC);
     /// Aligns to the NGX_LOG_DEBUG_MUTEX mask.
-    Mutex,
+    pub const Mutex: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_MUTEX);
     /// Aligns to the NGX_LOG_DEBUG_EVENT mask.
-    Event,
+    pub const Event: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_EVENT);
     /// Aligns to the NGX_LOG_DEBUG_HTTP mask.
-    Http,
+    pub const Http: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_HTTP);
     /// Aligns to the NGX_LOG_DEBUG_MAIL mask.
-    Mail,
+    pub const Mail: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_MAIL);
     /// Aligns to the NGX_LOG_DEBUG_STREAM mask.
-    Stream,
+    pub const Stream: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_STREAM);
     /// Aligns to the NGX_LOG_DEBUG_ALL mask.
-    All,
-}
-
-impl TryFrom<u32> for DebugMask {
-    type Error = u32;
-
-    fn try_from(value: u32) -> Result<Self, Self::Error> {
-        match value {
-            crate::ffi::NGX_LOG_DEBUG_CORE => Ok(DebugMask::Core),
-            crate::ffi::NGX_LOG_DEBUG_ALLOC => Ok(DebugMask::Alloc),
-            crate::ffi::NGX_LOG_DEBUG_MUTEX => Ok(DebugMask::Mutex),
-            crate::ffi::NGX_LOG_DEBUG_EVENT => Ok(DebugMask::Event),
-            crate::ffi::NGX_LOG_DEBUG_HTTP => Ok(DebugMask::Http),
-            crate::ffi::NGX_LOG_DEBUG_MAIL => Ok(DebugMask::Mail),
-            crate::ffi::NGX_LOG_DEBUG_STREAM => Ok(DebugMask::Stream),
-            crate::ffi::NGX_LOG_DEBUG_ALL => Ok(DebugMask::All),
-            _ => Err(0),
+    pub const All: DebugMask = DebugMask(crate::ffi::NGX_LOG_DEBUG_ALL);
+
+    /// The individual single-bit masks, in the order [`DebugMaskIter`] yields them.
+    const SINGLE_BITS: [DebugMask; 7] = [
+        DebugMask::Core,
+        DebugMask::Alloc,
+        DebugMask::Mutex,
+        DebugMask::Event,
+        DebugMask::Http,
+        DebugMask::Mail,
+        DebugMask::Stream,
+    ];
+
+    /// The raw `NGX_LOG_DEBUG_*` bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether `self` has all the bits set in `other`.
+    pub const fn contains(self, other: DebugMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Iterate over the individual single-bit masks set in `self`.
+    pub fn iter(self) -> DebugMaskIter {
+        DebugMaskIter { remaining: self }
+    }
+}
+
+impl ::std::ops::BitOr for DebugMask {
+    type Output = DebugMask;
+
+    fn bitor(self, rhs: DebugMask) -> DebugMask {
+        DebugMask(self.0 | rhs.0)
+    }
+}
+
+impl ::std::ops::BitAnd for DebugMask {
+    type Output = DebugMask;
+
+    fn bitand(self, rhs: DebugMask) -> DebugMask {
+        DebugMask(self.0 & rhs.0)
+    }
+}
+
+/// Iterator over the individual single-bit masks set in a [`DebugMask`], returned by
+/// [`DebugMask::iter`].
+pub struct DebugMaskIter {
+    remaining: DebugMask,
+}
+
+impl Iterator for DebugMaskIter {
+    type Item = DebugMask;
+
+    fn next(&mut self) -> Option<DebugMask> {
+        for bit in DebugMask::SINGLE_BITS {
+            if self.remaining.contains(bit) {
+                self.remaining.0 &= !bit.0;
+                return Some(bit);
+            }
         }
+        None
+    }
+}
+
+impl From<u32> for DebugMask {
+    /// Masks `value` with `NGX_LOG_DEBUG_ALL` and returns the resulting set of masks: the
+    /// severity bits below `NGX_LOG_DEBUG_FIRST` and the top bit are dropped, while bits of
+    /// `NGX_LOG_DEBUG_ALL` above `NGX_LOG_DEBUG_LAST` are kept, so [`DebugMask::All`] maps
+    /// back to itself. Unlike the plain-enum version this previously
+    /// replaced, this is lossless for arbitrary combinations (e.g. a log's `log_level`)
+    /// rather than failing on anything but a single known variant. `TryFrom<u32>` is
+    /// available too, via the standard library's blanket impl for conversions that can't
+    /// fail -- there's no fallible path here, so a hand-written `TryFrom` would just be an
+    /// infallible one in disguise.
+    fn from(value: u32) -> Self {
+        DebugMask(value & crate::ffi::NGX_LOG_DEBUG_ALL)
     }
 }
 
 impl From<DebugMask> for u32 {
     fn from(value: DebugMask) -> Self {
-        match value {
-            DebugMask::Core => crate::ffi::NGX_LOG_DEBUG_CORE,
-            DebugMask::Alloc => crate::ffi::NGX_LOG_DEBUG_ALLOC,
-            DebugMask::Mutex => crate::ffi::NGX_LOG_DEBUG_MUTEX,
-            DebugMask::Event => crate::ffi::NGX_LOG_DEBUG_EVENT,
-            DebugMask::Http => crate::ffi::NGX_LOG_DEBUG_HTTP,
-            DebugMask::Mail => crate::ffi::NGX_LOG_DEBUG_MAIL,
-            DebugMask::Stream => crate::ffi::NGX_LOG_DEBUG_STREAM,
-            DebugMask::All => crate::ffi::NGX_LOG_DEBUG_ALL,
+        value.0
+    }
+}
+
+/// Renders a byte slice as lowercase hex (`{:02x}` per byte) on demand, for logging opaque
+/// buffers -- session tickets, hashes, raw header bytes -- without allocating an
+/// intermediate `String` at the call site. Pass it straight into [`ngx_log_debug!`]:
+///
+/// ```ignore
+/// ngx_log_debug!(log, "ticket: {}", LogHex(&ticket));
+/// ```
+pub struct LogHex<'a>(pub &'a [u8]);
+
+impl ::std::fmt::Display for LogHex<'_> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Convenience wrapper around [`ngx_log_debug!`] for logging a byte slice as hex, analogous
+/// to rust-lightning's `log_bytes!`.
+#[macro_export]
+macro_rules! ngx_log_hex {
+    ( $log:expr, $bytes:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_debug!($log, "{} {}", ::std::format!($($arg)+), $crate::log::LogHex($bytes));
+    }
+}
+
+/// Renders an `ngx_str_t` for logging without assuming it is valid UTF-8: printable ASCII
+/// passes through, everything else is escaped as `\xNN`. Unlike [`LogHex`] this is meant
+/// for strings that are *usually* text (header values, URIs) but may carry stray bytes.
+pub struct LogNgxStr<'a>(pub &'a crate::ffi::ngx_str_t);
+
+impl ::std::fmt::Display for LogNgxStr<'_> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        // An unset `ngx_str_t` (e.g. `ngx_null_string`, or an absent header) is commonly
+        // `{ len: 0, data: ptr::null_mut() }`; `slice::from_raw_parts` requires `data` to be
+        // non-null and aligned even for a zero-length slice, so short-circuit before
+        // constructing it.
+        if self.0.len == 0 {
+            return Ok(());
         }
+        let bytes = unsafe { ::std::slice::from_raw_parts(self.0.data, self.0.len) };
+        for &byte in bytes {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                write!(f, "{}", byte as char)?;
+            } else {
+                write!(f, "\\x{byte:02x}")?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -187,4 +581,94 @@ mod tests {
         r = check_mask(DebugMask::Alloc, mock.log_level);
         assert!(!r);
     }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_ngx_log_level() {
+        assert_eq!(ngx_log_level(log::Level::Error), crate::ffi::NGX_LOG_ERR as crate::ffi::ngx_uint_t);
+        assert_eq!(ngx_log_level(log::Level::Warn), crate::ffi::NGX_LOG_WARN as crate::ffi::ngx_uint_t);
+        assert_eq!(ngx_log_level(log::Level::Info), crate::ffi::NGX_LOG_INFO as crate::ffi::ngx_uint_t);
+        // nginx has no severity more verbose than NGX_LOG_DEBUG, so both Debug and Trace map onto it.
+        assert_eq!(ngx_log_level(log::Level::Debug), crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t);
+        assert_eq!(ngx_log_level(log::Level::Trace), crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_ngx_log_level_to_filter_boundaries() {
+        let debug = crate::ffi::NGX_LOG_DEBUG as crate::ffi::ngx_uint_t;
+        let info = crate::ffi::NGX_LOG_INFO as crate::ffi::ngx_uint_t;
+        let warn = crate::ffi::NGX_LOG_WARN as crate::ffi::ngx_uint_t;
+        let err = crate::ffi::NGX_LOG_ERR as crate::ffi::ngx_uint_t;
+
+        assert_eq!(ngx_log_level_to_filter(debug), log::LevelFilter::Trace);
+        assert_eq!(ngx_log_level_to_filter(info), log::LevelFilter::Info);
+        assert_eq!(ngx_log_level_to_filter(warn), log::LevelFilter::Warn);
+        assert_eq!(ngx_log_level_to_filter(err), log::LevelFilter::Error);
+        assert_eq!(ngx_log_level_to_filter(err - 1), log::LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_push_kv_unquoted() {
+        let mut message = String::from("request failed");
+        push_kv(&mut message, "status", &502);
+        assert_eq!(message, "request failed status=502");
+    }
+
+    #[test]
+    fn test_push_kv_quotes_spaces() {
+        let mut message = String::new();
+        push_kv(&mut message, "upstream", &"backend one");
+        assert_eq!(message, " upstream=\"backend one\"");
+    }
+
+    #[test]
+    fn test_push_kv_escapes_embedded_quotes() {
+        let mut message = String::new();
+        push_kv(&mut message, "msg", &"say \"hi\" now");
+        assert_eq!(message, " msg=\"say \\\"hi\\\" now\"");
+    }
+
+    #[test]
+    fn test_log_hex() {
+        assert_eq!(LogHex(&[0xde, 0xad, 0xbe, 0xef]).to_string(), "deadbeef");
+        assert_eq!(LogHex(&[]).to_string(), "");
+    }
+
+    #[test]
+    fn test_log_ngx_str_null() {
+        // The common unset/absent representation: len 0, data null. Must not dereference.
+        let s = crate::ffi::ngx_str_t { len: 0, data: ::std::ptr::null_mut() };
+        assert_eq!(LogNgxStr(&s).to_string(), "");
+    }
+
+    #[test]
+    fn test_log_ngx_str_escapes_non_printable() {
+        let mut bytes = Vec::from(*b"ok\x01\x7f end");
+        let s = crate::ffi::ngx_str_t {
+            len: bytes.len(),
+            data: bytes.as_mut_ptr(),
+        };
+        assert_eq!(LogNgxStr(&s).to_string(), "ok\\x01\\x7f end");
+    }
+
+    #[test]
+    fn test_debug_mask_combination() {
+        let combined = DebugMask::Http | DebugMask::Event;
+        assert!(combined.contains(DebugMask::Http));
+        assert!(combined.contains(DebugMask::Event));
+        assert!(!combined.contains(DebugMask::Mail));
+        assert_eq!(combined.iter().collect::<Vec<_>>(), vec![DebugMask::Event, DebugMask::Http]);
+    }
+
+    #[test]
+    fn test_debug_mask_try_from_lossless() {
+        let combined = DebugMask::Http | DebugMask::Event;
+        let roundtrip = DebugMask::try_from(combined.bits()).unwrap();
+        assert_eq!(roundtrip, combined);
+
+        // bits outside NGX_LOG_DEBUG_ALL are masked off, not rejected.
+        let masked = DebugMask::try_from(u32::MAX).unwrap();
+        assert_eq!(masked.bits(), crate::ffi::NGX_LOG_DEBUG_ALL);
+    }
 }